@@ -19,75 +19,179 @@
 //! Substrate chain configurations.
 #![warn(missing_docs)]
 
-use std::{borrow::Cow, fs::File, path::PathBuf, sync::Arc, collections::HashMap};
+use std::{borrow::Cow, fs::File, io, path::PathBuf, sync::Arc, collections::HashMap};
 use serde::{Serialize, Deserialize};
+use sp_core::Bytes;
 use sp_core::storage::{StorageKey, StorageData, ChildInfo, Storage, StorageChild};
 use sp_runtime::BuildStorage;
 use serde_json as json;
+use once_cell::sync::OnceCell;
 use crate::{RuntimeGenesis, ChainType, extension::GetExtension, Properties};
 use sc_network::config::MultiaddrWithPeerId;
 use sc_telemetry::TelemetryEndpoints;
-use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_runtime::traits::Block as BlockT;
 
-enum GenesisSource<G> {
+enum GenesisSourceInner<G> {
 	File(PathBuf),
 	Binary(Cow<'static, [u8]>),
 	Factory(Arc<dyn Fn() -> G + Send + Sync>),
 	Storage(Storage),
+	/// A `base` genesis with a storage diff (`patch`) applied on top of it once
+	/// resolved, so a handful of keys can be overridden without forking the base
+	/// spec's genesis entirely.
+	Overlay(Box<GenesisSource<G>>, Storage),
 }
 
-impl<G> Clone for GenesisSource<G> {
+impl<G> Clone for GenesisSourceInner<G> {
 	fn clone(&self) -> Self {
 		match *self {
 			Self::File(ref path) => Self::File(path.clone()),
 			Self::Binary(ref d) => Self::Binary(d.clone()),
 			Self::Factory(ref f) => Self::Factory(f.clone()),
 			Self::Storage(ref s) => Self::Storage(s.clone()),
+			Self::Overlay(ref base, ref patch) => Self::Overlay(base.clone(), patch.clone()),
 		}
 	}
 }
 
+/// Where a chain's genesis comes from, together with a cache of its resolved form.
+///
+/// `resolve()` is potentially expensive (it may open and parse a multi-hundred-MB
+/// spec file, or re-run a runtime genesis factory), so the result is memoized behind
+/// an `Arc<OnceCell<_>>` the first time it's needed. Cloning a `GenesisSource` shares
+/// that cache, so a `ChainSpec::clone()` doesn't pay to resolve genesis again.
+struct GenesisSource<G> {
+	inner: GenesisSourceInner<G>,
+	cache: Arc<OnceCell<Genesis<G>>>,
+}
+
+impl<G> GenesisSource<G> {
+	fn new(inner: GenesisSourceInner<G>) -> Self {
+		GenesisSource { inner, cache: Arc::new(OnceCell::new()) }
+	}
+}
+
+impl<G> Clone for GenesisSource<G> {
+	fn clone(&self) -> Self {
+		GenesisSource { inner: self.inner.clone(), cache: self.cache.clone() }
+	}
+}
+
+/// Convert an already-resolved `Storage` into the hex/SCALE keyed `RawGenesis`
+/// representation used for raw genesis JSON.
+fn storage_to_raw_genesis(storage: &Storage) -> RawGenesis {
+	let top = storage.top
+		.iter()
+		.map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone())))
+		.collect();
+
+	let children_default = storage.children_default
+		.iter()
+		.map(|(k, child)|
+			 (
+				 StorageKey(k.clone()),
+				 child.data
+					.iter()
+					.map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone())))
+					.collect()
+			 )
+		)
+		.collect();
+
+	RawGenesis { top, children_default }
+}
+
+/// Convert a `RawGenesis` back into the `Storage` consumed by `BuildStorage`.
+fn raw_genesis_to_storage(raw: &RawGenesis) -> Storage {
+	Storage {
+		top: raw.top.iter().map(|(k, v)| (k.0.clone(), v.0.clone())).collect(),
+		children_default: raw.children_default.iter().map(|(storage_key, child_content)| {
+			let child_info = ChildInfo::new_default(storage_key.0.as_slice());
+			(
+				storage_key.0.clone(),
+				StorageChild {
+					data: child_content.iter().map(|(k, v)| (k.0.clone(), v.0.clone())).collect(),
+					child_info,
+				},
+			)
+		}).collect(),
+	}
+}
+
+/// Compute a self-consistent integrity checksum over `storage`, so it can be checked
+/// against a declared `genesis_state_root` to detect a tampered or truncated spec.
+///
+/// This runs `sp_io::storage::root()` under a `BasicExternalities` built straight
+/// from `storage`, which folds child tries into the top trie the same way every time
+/// this function is called, but not necessarily the same way the genesis block
+/// itself folds them when it is authored from this storage. Do not treat the result
+/// as the canonical block-header genesis state root.
+// `sp-io` and `sp-state-machine` must be declared as normal (non-dev) dependencies of
+// this crate for `BasicExternalities` and `sp_io::storage::root()` to link.
+fn compute_genesis_state_root(storage: &Storage) -> Vec<u8> {
+	sp_state_machine::BasicExternalities::new(storage.clone())
+		.execute_with(|| sp_io::storage::root())
+}
+
+/// Overlay a storage diff onto a raw genesis, in place. Keys in `patch` take
+/// precedence over any same-named key already in `raw`.
+fn overlay_raw_genesis(raw: &mut RawGenesis, patch: &Storage) {
+	raw.top.extend(
+		patch.top.iter().map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone())))
+	);
+
+	for (child_key, child) in &patch.children_default {
+		raw.children_default.entry(StorageKey(child_key.clone()))
+			.or_insert_with(HashMap::new)
+			.extend(child.data.iter().map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone()))));
+	}
+}
+
 impl<G: RuntimeGenesis> GenesisSource<G> {
+	/// Resolve the genesis, parsing the spec file or running the genesis factory at
+	/// most once: the result is cached the first time this is called.
+	fn resolve(&self) -> Result<&Genesis<G>, String> {
+		self.cache.get_or_try_init(|| self.inner.resolve())
+	}
+}
+
+impl<G: RuntimeGenesis> GenesisSourceInner<G> {
 	fn resolve(&self) -> Result<Genesis<G>, String> {
 		#[derive(Serialize, Deserialize)]
 		struct GenesisContainer<G> {
 			genesis: Genesis<G>,
 		}
 
+		fn resolve_patch<G>(genesis: Genesis<G>) -> Result<Genesis<G>, String> {
+			match genesis {
+				Genesis::Patch(patch) => Ok(Genesis::Raw(patch_to_raw_genesis(&patch)?)),
+				genesis => Ok(genesis),
+			}
+		}
+
 		match self {
 			Self::File(path) => {
 				let file = File::open(path)
 					.map_err(|e| format!("Error opening spec file: {}", e))?;
 				let genesis: GenesisContainer<G> = json::from_reader(file)
 					.map_err(|e| format!("Error parsing spec file: {}", e))?;
-				Ok(genesis.genesis)
+				resolve_patch(genesis.genesis)
 			},
 			Self::Binary(buf) => {
 				let genesis: GenesisContainer<G> = json::from_reader(buf.as_ref())
 					.map_err(|e| format!("Error parsing embedded file: {}", e))?;
-				Ok(genesis.genesis)
+				resolve_patch(genesis.genesis)
 			},
 			Self::Factory(f) => Ok(Genesis::Runtime(f())),
-			Self::Storage(storage) => {
-				let top = storage.top
-					.iter()
-					.map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone())))
-					.collect();
-
-				let children_default = storage.children_default
-					.iter()
-					.map(|(k, child)|
-						 (
-							 StorageKey(k.clone()),
-							 child.data
-								.iter()
-								.map(|(k, v)| (StorageKey(k.clone()), StorageData(v.clone())))
-								.collect()
-						 )
-					)
-					.collect();
-
-				Ok(Genesis::Raw(RawGenesis { top, children_default }))
+			Self::Storage(storage) => Ok(Genesis::Raw(storage_to_raw_genesis(storage))),
+			Self::Overlay(base, patch) => {
+				let mut raw = match base.resolve()? {
+					Genesis::Runtime(g) => storage_to_raw_genesis(&g.build_storage()?),
+					Genesis::Raw(raw) => raw.clone(),
+					Genesis::Patch(json_patch) => patch_to_raw_genesis(json_patch)?,
+				};
+				overlay_raw_genesis(&mut raw, patch);
+				Ok(Genesis::Raw(raw))
 			},
 		}
 	}
@@ -95,36 +199,51 @@ impl<G: RuntimeGenesis> GenesisSource<G> {
 
 impl<G: RuntimeGenesis, E> BuildStorage for ChainSpec<G, E> {
 	fn build_storage(&self) -> Result<Storage, String> {
-		match self.genesis.resolve()? {
-			Genesis::Runtime(gc) => gc.build_storage(),
-			Genesis::Raw(RawGenesis { top: map, children_default: children_map }) => Ok(Storage {
-				top: map.into_iter().map(|(k, v)| (k.0, v.0)).collect(),
-				children_default: children_map.into_iter().map(|(storage_key, child_content)| {
-					let child_info = ChildInfo::new_default(storage_key.0.as_slice());
-					(
-						storage_key.0,
-						StorageChild {
-							data: child_content.into_iter().map(|(k, v)| (k.0, v.0)).collect(),
-							child_info,
-						},
-					)
-				}).collect(),
-			}),
+		let storage = match self.genesis.resolve()? {
+			Genesis::Runtime(gc) => gc.build_storage()?,
+			Genesis::Raw(raw) => raw_genesis_to_storage(raw),
+			Genesis::Patch(patch) => raw_genesis_to_storage(&patch_to_raw_genesis(patch)?),
+		};
+
+		if let Some(expected_root) = &self.client_spec.genesis_state_root {
+			let actual_root = compute_genesis_state_root(&storage);
+			if actual_root != expected_root.0 {
+				return Err(format!(
+					"Genesis state integrity check failed: spec declares {}, computed {}",
+					sp_core::bytes::to_hex(&expected_root.0, false),
+					sp_core::bytes::to_hex(&actual_root, false),
+				));
+			}
 		}
+
+		Ok(storage)
 	}
 
 	fn assimilate_storage(
 		&self,
-		_: &mut Storage,
+		storage: &mut Storage,
 	) -> Result<(), String> {
-		Err("`assimilate_storage` not implemented for `ChainSpec`.".into())
+		let genesis_storage = self.build_storage()?;
+
+		storage.top.extend(genesis_storage.top);
+		for (child_key, child) in genesis_storage.children_default {
+			storage.children_default.entry(child_key)
+				.or_insert_with(|| StorageChild {
+					data: Default::default(),
+					child_info: child.child_info.clone(),
+				})
+				.data
+				.extend(child.data);
+		}
+
+		Ok(())
 	}
 }
 
 pub type GenesisStorage = HashMap<StorageKey, StorageData>;
 
 /// Raw storage content for genesis block.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
 pub struct RawGenesis {
@@ -138,6 +257,69 @@ pub struct RawGenesis {
 enum Genesis<G> {
 	Runtime(G),
 	Raw(RawGenesis),
+	/// Same storage as `Raw`, but authored as typed JSON values keyed by hex storage
+	/// key rather than pre-encoded `StorageData`. SCALE-encoded into a `Raw` genesis
+	/// the first time it is resolved; see [`patch_to_raw_genesis`].
+	Patch(json::Map<String, json::Value>),
+}
+
+/// Convert a JSON value into its SCALE-encoded byte representation for a genesis
+/// patch: booleans become a single `0`/`1` byte, numbers (and hex strings that parse
+/// as numbers) become a little-endian SCALE integer, other strings become a
+/// SCALE-encoded byte vector, and arrays/objects recurse into a SCALE-encoded
+/// sequence of their (already encoded) elements.
+fn json_to_scale(value: &json::Value) -> Result<Vec<u8>, String> {
+	use codec::Encode;
+
+	fn as_integer(s: &str) -> Option<Vec<u8>> {
+		let digits = s.strip_prefix("0x")?;
+		if let Ok(n) = u64::from_str_radix(digits, 16) {
+			Some(n.encode())
+		} else {
+			u128::from_str_radix(digits, 16).ok().map(|n| n.encode())
+		}
+	}
+
+	match value {
+		json::Value::Bool(b) => Ok(vec![if *b { 1 } else { 0 }]),
+		json::Value::Number(n) => {
+			if let Some(n) = n.as_u64() {
+				Ok(n.encode())
+			} else if let Ok(n) = n.to_string().parse::<u128>() {
+				Ok(n.encode())
+			} else {
+				Err(format!("Unsupported genesis patch number: {}", n))
+			}
+		},
+		json::Value::String(s) => Ok(as_integer(s).unwrap_or_else(|| s.as_bytes().to_vec().encode())),
+		json::Value::Array(values) => {
+			let items = values.iter().map(json_to_scale).collect::<Result<Vec<_>, _>>()?;
+			let mut encoded = codec::Compact(items.len() as u64).encode();
+			items.into_iter().for_each(|item| encoded.extend(item));
+			Ok(encoded)
+		},
+		json::Value::Object(map) => {
+			let items = map.values().map(json_to_scale).collect::<Result<Vec<_>, _>>()?;
+			let mut encoded = codec::Compact(items.len() as u64).encode();
+			items.into_iter().for_each(|item| encoded.extend(item));
+			Ok(encoded)
+		},
+		json::Value::Null => Err("Genesis patch values cannot be null".into()),
+	}
+}
+
+/// Convert a `Genesis::Patch` map (hex storage key -> typed JSON value) into the
+/// `RawGenesis` fed to `BuildStorage`, SCALE-encoding every value along the way.
+fn patch_to_raw_genesis(patch: &json::Map<String, json::Value>) -> Result<RawGenesis, String> {
+	let top = patch.iter()
+		.map(|(key, value)| {
+			let key: StorageKey = json::from_value(json::Value::String(key.clone()))
+				.map_err(|e| format!("Invalid genesis patch storage key `{}`: {}", key, e))?;
+			Ok((key, StorageData(json_to_scale(value)?)))
+		})
+		.collect::<Result<_, String>>()?;
+
+	Ok(RawGenesis { top, children_default: HashMap::new() })
 }
 
 /// A configuration of a client. Does not include runtime storage initialization.
@@ -160,6 +342,18 @@ struct ClientSpec<E> {
 	#[serde(skip_serializing)]
 	genesis: serde::de::IgnoredAny,
 	light_sync_state: Option<SerializableLightSyncState>,
+	/// Known runtime code overrides, keyed by the block number or block hash (as a
+	/// hex/decimal string) at which they should take effect.
+	#[serde(default)]
+	code_substitutes: HashMap<String, Bytes>,
+	/// An integrity checksum over the genesis storage, computed by
+	/// `compute_genesis_state_root`. When present, `build_storage` recomputes it from
+	/// the assembled genesis storage and refuses to build a tampered or truncated
+	/// spec. Despite the name, this is not guaranteed to equal the canonical
+	/// block-header genesis state root once child tries are folded into the header
+	/// root differently than `compute_genesis_state_root` folds them here; treat it
+	/// as a spec-content checksum, not as the header root.
+	genesis_state_root: Option<Bytes>,
 }
 
 /// A type denoting empty extensions.
@@ -225,6 +419,27 @@ impl<G, E> ChainSpec<G, E> {
 		&self.client_spec.extensions
 	}
 
+	/// Overlay `other` on top of this spec's genesis, so a key present in `other`
+	/// overrides the same key coming from the base genesis. Useful for deriving a
+	/// customized testnet from a canonical live spec by overriding only a handful
+	/// of keys, without having to fork the base spec's genesis entirely.
+	pub fn merge_overlay(&mut self, other: &Storage) {
+		let base = self.genesis.clone();
+		self.genesis = GenesisSource::new(GenesisSourceInner::Overlay(Box::new(base), other.clone()));
+	}
+
+	/// Known runtime code overrides, keyed by the block number or block hash at which
+	/// the substitute should be used instead of the on-chain runtime code.
+	pub fn code_substitutes(&self) -> &HashMap<String, Bytes> {
+		&self.client_spec.code_substitutes
+	}
+
+	/// The spec's declared genesis storage integrity checksum, if any. Not guaranteed
+	/// to equal the canonical block-header genesis state root.
+	pub fn genesis_state_root(&self) -> Option<&Bytes> {
+		self.client_spec.genesis_state_root.as_ref()
+	}
+
 	/// Create hardcoded spec.
 	pub fn from_genesis<F: Fn() -> G + 'static + Send + Sync>(
 		name: &str,
@@ -249,11 +464,13 @@ impl<G, E> ChainSpec<G, E> {
 			consensus_engine: (),
 			genesis: Default::default(),
 			light_sync_state: None,
+			code_substitutes: Default::default(),
+			genesis_state_root: None,
 		};
 
 		ChainSpec {
 			client_spec,
-			genesis: GenesisSource::Factory(Arc::new(constructor)),
+			genesis: GenesisSource::new(GenesisSourceInner::Factory(Arc::new(constructor))),
 		}
 	}
 
@@ -280,7 +497,7 @@ impl<G, E: serde::de::DeserializeOwned> ChainSpec<G, E> {
 			.map_err(|e| format!("Error parsing spec file: {}", e))?;
 		Ok(ChainSpec {
 			client_spec,
-			genesis: GenesisSource::Binary(json),
+			genesis: GenesisSource::new(GenesisSourceInner::Binary(json)),
 		})
 	}
 
@@ -292,43 +509,127 @@ impl<G, E: serde::de::DeserializeOwned> ChainSpec<G, E> {
 			.map_err(|e| format!("Error parsing spec file: {}", e))?;
 		Ok(ChainSpec {
 			client_spec,
-			genesis: GenesisSource::File(path),
+			genesis: GenesisSource::new(GenesisSourceInner::File(path)),
 		})
 	}
 }
 
-#[derive(Serialize, Deserialize)]
-struct JsonContainer<G, E> {
+/// Either a reference to an already-resolved `Genesis`, a freshly built one, or a raw
+/// `Storage` built from a runtime genesis. `OwnedStorage` is serialized directly off
+/// the `Storage` maps (see `RawGenesisRef`) instead of first collecting it into a
+/// `RawGenesis`, so `json_container` can hand a runtime genesis to `as_json_writer`
+/// without building an intermediate `RawGenesis` copy of the whole genesis storage.
+enum GenesisRef<'a, G> {
+	Borrowed(&'a Genesis<G>),
+	Owned(Genesis<G>),
+	OwnedStorage(Storage),
+}
+
+impl<'a, G: Serialize> Serialize for GenesisRef<'a, G> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeMap;
+
+		match self {
+			GenesisRef::Borrowed(genesis) => genesis.serialize(serializer),
+			GenesisRef::Owned(genesis) => genesis.serialize(serializer),
+			GenesisRef::OwnedStorage(storage) => {
+				let mut map = serializer.serialize_map(Some(1))?;
+				map.serialize_entry("raw", &RawGenesisRef(storage))?;
+				map.end()
+			},
+		}
+	}
+}
+
+/// Serializes a `Storage` in the same shape as `RawGenesis` (hex-keyed `top` /
+/// `childrenDefault` maps), reading directly out of `storage` entry-by-entry instead
+/// of first collecting it into a `RawGenesis`. This is what lets `as_json_writer`
+/// stream a runtime genesis's storage straight to the writer without ever holding a
+/// second full copy of it in memory.
+struct RawGenesisRef<'a>(&'a Storage);
+
+impl<'a> Serialize for RawGenesisRef<'a> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::{SerializeMap, SerializeStruct};
+
+		struct StorageMap<'a>(&'a HashMap<Vec<u8>, Vec<u8>>);
+
+		impl<'a> Serialize for StorageMap<'a> {
+			fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+				let mut map = serializer.serialize_map(Some(self.0.len()))?;
+				for (k, v) in self.0.iter() {
+					map.serialize_entry(
+						&sp_core::bytes::to_hex(k, false),
+						&sp_core::bytes::to_hex(v, false),
+					)?;
+				}
+				map.end()
+			}
+		}
+
+		struct ChildrenMap<'a>(&'a HashMap<Vec<u8>, StorageChild>);
+
+		impl<'a> Serialize for ChildrenMap<'a> {
+			fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+				let mut map = serializer.serialize_map(Some(self.0.len()))?;
+				for (k, child) in self.0.iter() {
+					map.serialize_entry(&sp_core::bytes::to_hex(k, false), &StorageMap(&child.data))?;
+				}
+				map.end()
+			}
+		}
+
+		let mut state = serializer.serialize_struct("RawGenesis", 2)?;
+		state.serialize_field("top", &StorageMap(&self.0.top))?;
+		state.serialize_field("childrenDefault", &ChildrenMap(&self.0.children_default))?;
+		state.end()
+	}
+}
+
+/// Either a reference to the spec's own `ClientSpec`, or an adjusted copy of it (e.g.
+/// with an auto-computed `genesis_state_root` for a raw dump).
+enum ClientSpecRef<'a, E> {
+	Borrowed(&'a ClientSpec<E>),
+	Owned(ClientSpec<E>),
+}
+
+impl<'a, E: Serialize> Serialize for ClientSpecRef<'a, E> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			ClientSpecRef::Borrowed(client_spec) => client_spec.serialize(serializer),
+			ClientSpecRef::Owned(client_spec) => client_spec.serialize(serializer),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct JsonContainer<'a, G, E> {
 	#[serde(flatten)]
-	client_spec: ClientSpec<E>,
-	genesis: Genesis<G>,
+	client_spec: ClientSpecRef<'a, E>,
+	genesis: GenesisRef<'a, G>,
 }
 
 impl<G: RuntimeGenesis, E: serde::Serialize + Clone + 'static> ChainSpec<G, E> {
-	fn json_container(&self, raw: bool) -> Result<JsonContainer<G, E>, String> {
-		let genesis = match (raw, self.genesis.resolve()?) {
+	fn json_container(&self, raw: bool) -> Result<JsonContainer<'_, G, E>, String> {
+		let resolved = self.genesis.resolve()?;
+
+		let (client_spec, genesis) = match (raw, resolved) {
 			(true, Genesis::Runtime(g)) => {
 				let storage = g.build_storage()?;
-				let top = storage.top.into_iter()
-					.map(|(k, v)| (StorageKey(k), StorageData(v)))
-					.collect();
-				let children_default = storage.children_default.into_iter()
-					.map(|(sk, child)| (
-						StorageKey(sk),
-						child.data.into_iter()
-							.map(|(k, v)| (StorageKey(k), StorageData(v)))
-							.collect(),
-					))
-					.collect();
-
-				Genesis::Raw(RawGenesis { top, children_default })
+				let mut client_spec = self.client_spec.clone();
+				client_spec.genesis_state_root = Some(Bytes(compute_genesis_state_root(&storage)));
+				(ClientSpecRef::Owned(client_spec), GenesisRef::OwnedStorage(storage))
+			},
+			(true, Genesis::Raw(raw_genesis)) => {
+				let storage = raw_genesis_to_storage(raw_genesis);
+				let mut client_spec = self.client_spec.clone();
+				client_spec.genesis_state_root = Some(Bytes(compute_genesis_state_root(&storage)));
+				(ClientSpecRef::Owned(client_spec), GenesisRef::Borrowed(resolved))
 			},
-			(_, genesis) => genesis,
+			(_, genesis) => (ClientSpecRef::Borrowed(&self.client_spec), GenesisRef::Borrowed(genesis)),
 		};
-		Ok(JsonContainer {
-			client_spec: self.client_spec.clone(),
-			genesis,
-		})
+
+		Ok(JsonContainer { client_spec, genesis })
 	}
 
 	/// Dump to json string.
@@ -344,6 +645,19 @@ impl<G: RuntimeGenesis, E: serde::Serialize + Clone + 'static> ChainSpec<G, E> {
 		json::to_value(container)
 			.map_err(|e| format!("Error generating spec json: {}", e))
 	}
+
+	/// Stream the spec as JSON directly to `writer`, without ever materializing the
+	/// whole document as an in-memory `String` first. For a raw dump of a runtime
+	/// genesis, the built `Storage` is also serialized directly (see
+	/// [`RawGenesisRef`]) rather than being collected into an intermediate
+	/// `RawGenesis`. Computing the `genesis_state_root` for a raw dump still clones
+	/// the built `Storage` once (see `compute_genesis_state_root`), so a second full
+	/// copy of the genesis state exists while that root is computed.
+	pub fn as_json_writer(&self, raw: bool, writer: impl io::Write) -> Result<(), String> {
+		let container = self.json_container(raw)?;
+		json::to_writer(writer, &container)
+			.map_err(|e| format!("Error generating spec json: {}", e))
+	}
 }
 
 impl<G, E> crate::ChainSpec for ChainSpec<G, E>
@@ -404,7 +718,7 @@ where
 	}
 
 	fn set_storage(&mut self, storage: Storage) {
-		self.genesis = GenesisSource::Storage(storage);
+		self.genesis = GenesisSource::new(GenesisSourceInner::Storage(storage));
 	}
 
 	fn set_light_sync_state(&mut self, light_sync_state: SerializableLightSyncState) {
@@ -416,15 +730,39 @@ where
 	}
 }
 
+/// A piece of light-client checkpoint state contributed by a single consensus engine.
+///
+/// Each consensus engine that wants light clients to be able to fast-sync into the
+/// middle of a chain implements this for whatever state it needs to hand over (an
+/// epoch changes tree, an authority set, ...) and registers it under a unique name in
+/// [`LightSyncState`]. This keeps `LightSyncState` itself free of any dependency on a
+/// particular consensus engine, so a node template only pulls in the engines it uses.
+pub trait LightSyncStateComponent: Sized {
+	/// Encode this component into an opaque storage blob.
+	fn to_storage_data(&self) -> StorageData;
+
+	/// Decode this component from an opaque storage blob.
+	fn from_storage_data(data: &StorageData) -> Result<Self, codec::Error>;
+}
+
+impl<T: codec::Encode + codec::Decode> LightSyncStateComponent for T {
+	fn to_storage_data(&self) -> StorageData {
+		use codec::Encode;
+		StorageData(self.encode())
+	}
+
+	fn from_storage_data(data: &StorageData) -> Result<Self, codec::Error> {
+		codec::Decode::decode(&mut &data.0[..])
+	}
+}
+
 /// Hardcoded infomation that allows light clients to sync quickly.
 pub struct LightSyncState<Block: BlockT> {
 	/// The header of the best finalized block.
 	pub finalized_block_header: <Block as BlockT>::Header,
-	/// The epoch changes tree for babe.
-	pub babe_epoch_changes: sc_consensus_epochs::EpochChangesFor<Block, sc_consensus_babe::Epoch>,
-	pub babe_finalized_block_weight: sp_consensus_babe::BabeBlockWeight,
-	/// The authority set for grandpa.
-	pub grandpa_authority_set: sc_finality_grandpa::AuthoritySet<<Block as BlockT>::Hash, NumberFor<Block>>,
+	/// Opaque, consensus-specific checkpoints keyed by a unique component name
+	/// (e.g. `"babe_epoch_changes"`, `"grandpa_authority_set"`).
+	pub components: HashMap<String, StorageData>,
 }
 
 impl<Block: BlockT> LightSyncState<Block> {
@@ -434,12 +772,7 @@ impl<Block: BlockT> LightSyncState<Block> {
 
 		SerializableLightSyncState {
 			finalized_block_header: StorageData(self.finalized_block_header.encode()),
-			babe_epoch_changes:
-				StorageData(self.babe_epoch_changes.encode()),
-			babe_finalized_block_weight:
-				self.babe_finalized_block_weight,
-			grandpa_authority_set:
-				StorageData(self.grandpa_authority_set.encode()),
+			components: self.components.clone(),
 		}
 	}
 
@@ -447,25 +780,28 @@ impl<Block: BlockT> LightSyncState<Block> {
 	pub fn from_serializable(serialized: &SerializableLightSyncState) -> Result<Self, codec::Error> {
 		Ok(Self {
 			finalized_block_header: codec::Decode::decode(&mut &serialized.finalized_block_header.0[..])?,
-			babe_epoch_changes:
-				codec::Decode::decode(&mut &serialized.babe_epoch_changes.0[..])?,
-			babe_finalized_block_weight:
-				serialized.babe_finalized_block_weight,
-			grandpa_authority_set:
-				codec::Decode::decode(&mut &serialized.grandpa_authority_set.0[..])?,
+			components: serialized.components.clone(),
 		})
 	}
+
+	/// Fetch and decode a named component (e.g. the BABE epoch changes tree).
+	pub fn component<T: LightSyncStateComponent>(&self, name: &str) -> Result<Option<T>, codec::Error> {
+		self.components.get(name).map(|data| T::from_storage_data(data)).transpose()
+	}
+
+	/// Encode and register a named component (e.g. the GRANDPA authority set).
+	pub fn set_component<T: LightSyncStateComponent>(&mut self, name: &str, value: &T) {
+		self.components.insert(name.to_owned(), value.to_storage_data());
+	}
 }
 
 /// The serializable form of `LightSyncState`. Created using `LightSyncState::serialize`.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
 pub struct SerializableLightSyncState {
 	finalized_block_header: StorageData,
-	babe_epoch_changes: StorageData,
-	babe_finalized_block_weight: sp_consensus_babe::BabeBlockWeight,
-	grandpa_authority_set: StorageData,
+	components: HashMap<String, StorageData>,
 }
 
 #[cfg(test)]
@@ -518,4 +854,167 @@ mod tests {
 
 		assert_eq!(spec.extensions().my_property, "Test Extension");
 	}
+
+	#[test]
+	fn should_round_trip_code_substitutes() {
+		let json = r#"{
+			"name": "Test",
+			"id": "test",
+			"chainType": "Live",
+			"bootNodes": [],
+			"telemetryEndpoints": null,
+			"protocolId": null,
+			"properties": null,
+			"consensusEngine": null,
+			"lightSyncState": null,
+			"codeSubstitutes": { "1000": "0x0102030405" },
+			"genesis": { "raw": { "top": {}, "childrenDefault": {} } }
+		}"#;
+
+		let mut expected = HashMap::new();
+		expected.insert("1000".to_owned(), Bytes(vec![0x01, 0x02, 0x03, 0x04, 0x05]));
+
+		let spec = TestSpec::from_json_bytes(Cow::Owned(json.as_bytes().to_vec())).unwrap();
+		assert_eq!(spec.code_substitutes(), &expected);
+
+		let reparsed = TestSpec::from_json_bytes(Cow::Owned(
+			spec.as_json(true).unwrap().into_bytes()
+		)).unwrap();
+		assert_eq!(reparsed.code_substitutes(), spec.code_substitutes());
+	}
+
+	#[test]
+	fn json_to_scale_encodes_typed_values() {
+		use codec::Encode;
+
+		assert_eq!(json_to_scale(&json::json!(true)).unwrap(), vec![1]);
+		assert_eq!(json_to_scale(&json::json!(false)).unwrap(), vec![0]);
+		assert_eq!(json_to_scale(&json::json!(42u64)).unwrap(), 42u64.encode());
+		assert_eq!(json_to_scale(&json::json!("0x2a")).unwrap(), 42u64.encode());
+		assert_eq!(json_to_scale(&json::json!("hello")).unwrap(), b"hello".to_vec().encode());
+		assert!(json_to_scale(&json::json!(null)).is_err());
+	}
+
+	#[test]
+	fn should_resolve_genesis_patch() {
+		use codec::Encode;
+
+		let mut patch = json::Map::new();
+		patch.insert("0x0102".to_owned(), json::json!("0x2a"));
+		patch.insert("0x0304".to_owned(), json::json!("hello"));
+
+		let raw = patch_to_raw_genesis(&patch).unwrap();
+		assert_eq!(
+			raw.top.get(&StorageKey(vec![0x01, 0x02])).unwrap(),
+			&StorageData(42u64.encode()),
+		);
+		assert_eq!(
+			raw.top.get(&StorageKey(vec![0x03, 0x04])).unwrap(),
+			&StorageData(b"hello".to_vec().encode()),
+		);
+	}
+
+	fn test_spec() -> TestSpec {
+		let mut genesis = HashMap::new();
+		genesis.insert("foo".to_owned(), "bar".to_owned());
+
+		TestSpec::from_genesis(
+			"Test",
+			"test",
+			ChainType::Live,
+			move || Genesis(genesis.clone()),
+			vec![],
+			None,
+			None,
+			None,
+			None,
+		)
+	}
+
+	#[test]
+	fn should_assimilate_storage_on_top_of_existing_keys() {
+		let spec = test_spec();
+		let mut storage = Storage {
+			top: vec![(b"untouched".to_vec(), b"kept".to_vec())].into_iter().collect(),
+			children_default: Default::default(),
+		};
+
+		spec.assimilate_storage(&mut storage).unwrap();
+
+		assert_eq!(storage.top.get(b"untouched".as_ref()), Some(&b"kept".to_vec()));
+		assert_eq!(storage.top.get(b"foo".as_ref()), Some(&b"bar".to_vec()));
+	}
+
+	#[test]
+	fn should_merge_overlay_on_top_of_base_genesis() {
+		let mut spec = test_spec();
+		let overlay = Storage {
+			top: vec![(b"foo".to_vec(), b"overridden".to_vec())].into_iter().collect(),
+			children_default: Default::default(),
+		};
+
+		spec.merge_overlay(&overlay);
+
+		let storage = spec.build_storage().unwrap();
+		assert_eq!(storage.top.get(b"foo".as_ref()), Some(&b"overridden".to_vec()));
+	}
+
+	#[test]
+	fn should_resolve_genesis_at_most_once() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+		let spec = TestSpec::from_genesis(
+			"Test",
+			"test",
+			ChainType::Live,
+			|| {
+				CALLS.fetch_add(1, Ordering::SeqCst);
+				Genesis(HashMap::new())
+			},
+			vec![],
+			None,
+			None,
+			None,
+			None,
+		);
+
+		spec.build_storage().unwrap();
+		spec.build_storage().unwrap();
+		spec.clone().build_storage().unwrap();
+
+		assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn as_json_writer_matches_as_json_value() {
+		let spec = test_spec();
+		let mut buf = Vec::new();
+
+		spec.as_json_writer(false, &mut buf).unwrap();
+
+		let streamed: json::Value = json::from_slice(&buf).unwrap();
+		assert_eq!(streamed, spec.as_json_value(false).unwrap());
+	}
+
+	#[test]
+	fn should_populate_and_verify_genesis_state_root() {
+		let spec = test_spec();
+		let raw_json = spec.as_json(true).unwrap();
+		let with_root = TestSpec::from_json_bytes(Cow::Owned(raw_json.into_bytes())).unwrap();
+
+		assert!(with_root.genesis_state_root().is_some());
+		with_root.build_storage().unwrap();
+	}
+
+	#[test]
+	fn should_reject_tampered_genesis_state_root() {
+		let spec = test_spec();
+		let mut value = spec.as_json_value(true).unwrap();
+		value["genesisStateRoot"] = json::json!("0x00");
+		let tampered = TestSpec::from_json_bytes(Cow::Owned(json::to_vec(&value).unwrap())).unwrap();
+
+		assert!(tampered.build_storage().is_err());
+	}
 }